@@ -0,0 +1,624 @@
+#pragma once
+
+#include <algorithm>
+#include <compare>
+#include <format>
+#include <functional>
+#include <limits>
+#include <map>
+#include <optional>
+#include <span>
+#include <stdexcept>
+#include <stdint.h>
+#include <string>
+#include <string_view>
+#include <utility>
+#include <vector>
+
+struct LineContent {
+  std::string_view head;
+  std::string_view tail;
+
+  std::string flattern() const {
+    std::string ret;
+    ret.reserve(head.length() + tail.length());
+    ret.append(head);
+    ret.append(ret);
+    return ret;
+  }
+};
+
+struct Position {
+  uint64_t row;
+  uint64_t column;
+
+  // Orders by row first; column only matters when rows are equal.
+  auto operator<=>(const Position &) const = default;
+
+  // The bounds may be given in either order.
+  constexpr Position clamp(Position min, Position max) const {
+    if (max < min) {
+      std::swap(min, max);
+    }
+    return std::clamp(*this, min, max);
+  }
+
+  constexpr bool is_empty_range(Position other) const {
+    return *this == other;
+  }
+};
+
+struct ByteRange {
+  uint64_t start;
+  uint64_t end;
+
+  bool operator==(const ByteRange &) const = default;
+};
+
+struct LineEndingStats {
+  uint64_t lf = 0;
+  uint64_t crlf = 0;
+  uint64_t cr = 0;
+  // The chunk's first line is a bare "\n".
+  bool starts_with_lf = false;
+  // The chunk's last byte is a '\r', which may pair with a leading '\n' in
+  // the next chunk.
+  bool ends_with_cr = false;
+  // That trailing '\r' was counted in `cr` (lone-CR mode).
+  bool ends_with_counted_cr = false;
+  // No bytes have been counted, so the seam flags above carry no information.
+  bool empty = true;
+
+  // Appends the stats of the chunk that follows. A "\r\n" split across the
+  // seam was counted as an LF (and, in lone-CR mode, a CR); it is recounted
+  // as one CRLF here. Empty stats on either side leave the other unchanged.
+  LineEndingStats &operator+=(const LineEndingStats &other) {
+    if (other.empty) {
+      return *this;
+    }
+    if (empty) {
+      return *this = other;
+    }
+    lf += other.lf;
+    crlf += other.crlf;
+    cr += other.cr;
+    if (ends_with_cr && other.starts_with_lf) {
+      lf--;
+      crlf++;
+      if (ends_with_counted_cr) {
+        cr--;
+      }
+    }
+    ends_with_cr = other.ends_with_cr;
+    ends_with_counted_cr = other.ends_with_counted_cr;
+    return *this;
+  }
+
+  bool mixed() const { return (lf > 0) + (crlf > 0) + (cr > 0) > 1; }
+};
+
+struct ChunkOptions {
+  // A '\r' not followed by '\n' also ends a line. A '\r' at the very end of
+  // the chunk is left pending (see Chunk::ends_with_pending_cr), since the
+  // following '\n' (if any) is not visible here.
+  bool lone_cr_is_newline = false;
+  // Leave a leading UTF-8 BOM out of the first line. Only meaningful for the
+  // first chunk of a file; offsets in start_offset still count the BOM.
+  bool skip_bom = false;
+};
+
+struct Chunk {
+  std::string content;
+  std::vector<uint32_t> start_offset;
+  ChunkOptions options;
+
+  static constexpr std::string_view utf8_bom = "\xEF\xBB\xBF";
+
+  // Throws std::length_error if `content` is larger than 4 GiB, since
+  // in-chunk offsets are uint32_t (file-level positions stay uint64_t).
+  static Chunk make(std::string content, ChunkOptions options = {}) {
+    if (content.size() > std::numeric_limits<uint32_t>::max()) {
+      throw std::length_error("chunk larger than 4 GiB");
+    }
+    std::vector<uint32_t> start_offset;
+
+    uint32_t content_start = 0;
+    if (options.skip_bom && content.starts_with(utf8_bom)) {
+      content_start = utf8_bom.size();
+    }
+
+    start_offset.push_back(content_start);
+    for (uint32_t offset = content_start; offset < content.size(); offset++) {
+      if (content[offset] == '\n') {
+        start_offset.push_back(offset + 1);
+      } else if (options.lone_cr_is_newline && content[offset] == '\r' &&
+                 offset + 1 < content.size() && content[offset + 1] != '\n') {
+        start_offset.push_back(offset + 1);
+      }
+    }
+    if (start_offset.back() != content.size()) {
+      start_offset.push_back(content.size());
+    }
+    return Chunk{std::move(content), std::move(start_offset), options};
+  }
+
+  // The raw chunk bytes, including a leading BOM and any partial last line.
+  std::string_view data() const { return content; }
+
+  uint32_t content_start_offset() const { return start_offset.front(); }
+
+  bool ends_with_newline() const {
+    return !content.empty() && content.back() == '\n';
+  }
+
+  // In lone-CR mode, a trailing '\r' is either a lone CR or the first half of
+  // a "\r\n" split across chunks, so the last line is left incomplete. The
+  // caller that sees the next chunk decides: if it starts with '\n' the line
+  // continues there as usual, otherwise the '\r' ended the line.
+  bool ends_with_pending_cr() const {
+    return options.lone_cr_is_newline && !content.empty() &&
+           content.back() == '\r';
+  }
+
+  bool continue_to_next_chunk() const { return ends_with_newline(); }
+
+  // An empty chunk has no incomplete line, so it reports true.
+  bool last_line_complete() const {
+    return get_line_count() == 0 || ends_with_newline();
+  }
+
+  uint32_t get_line_count() const { return start_offset.size() - 1; }
+
+  std::string_view get_line_content(uint32_t idx) const {
+    if (idx >= get_line_count()) {
+      return {};
+    }
+    auto start = start_offset[idx];
+    auto end = start_offset[idx + 1];
+    return {content.data() + start, end - start};
+  }
+
+  std::string_view get_first_line_view() const { return get_line_content(0); }
+  std::string_view get_last_line_view() const {
+    return get_line_content(get_line_count() - 1);
+  }
+
+  // Counts terminators of the lines in this chunk; an unterminated last line
+  // is not counted, except that in lone-CR mode a pending '\r' counts as a CR.
+  // Summing the stats of consecutive chunks with += fixes up a "\r\n" split
+  // across the seam.
+  LineEndingStats line_ending_stats() const {
+    LineEndingStats stats;
+    for (uint32_t idx = 0; idx < get_line_count(); idx++) {
+      auto line = get_line_content(idx);
+      if (line.ends_with("\r\n")) {
+        stats.crlf++;
+      } else if (line.ends_with('\n')) {
+        stats.lf++;
+      } else if (options.lone_cr_is_newline && line.ends_with('\r')) {
+        stats.cr++;
+      }
+    }
+    stats.starts_with_lf = get_first_line_view() == "\n";
+    stats.ends_with_cr = !content.empty() && content.back() == '\r';
+    stats.ends_with_counted_cr = ends_with_pending_cr();
+    stats.empty = content.empty();
+    return stats;
+  }
+
+  Position calc_end(Position start) const {
+    auto end = start;
+    if (get_line_count() == 0) {
+      return end;
+    }
+
+    auto last_line_idx = get_line_count() - 1;
+
+    end.row += last_line_idx;
+    if (end.row != start.row) {
+      end.column = 0;
+    }
+
+    end.column += get_line_content(last_line_idx).length();
+
+    if (!continue_to_next_chunk()) {
+      end.row += 1;
+      end.column = 0;
+    }
+
+    return end;
+  }
+
+  Position calc_backward_start() const {
+    auto pos = Position{};
+    if (get_line_count() == 0) {
+      return pos;
+    }
+    if (continue_to_next_chunk()) {
+      pos.column += get_line_content(get_line_count() - 1).length();
+    }
+    return pos;
+  }
+
+  Position calc_backward_end(Position start) const {
+    auto end = start;
+    if (get_line_count() == 0) {
+      return end;
+    }
+
+    end.row += get_line_count() - 1;
+    if (continue_to_next_chunk()) {
+      end.row += 1;
+    }
+
+    if (end.row != start.row) {
+      end.column = 0;
+      if (content[content_start_offset()] != '\n') {
+        end.column = get_first_line_view().length() - 1;
+      }
+    } else {
+      end.column += get_last_line_view().length();
+    }
+    return end;
+  }
+};
+
+// Returns the column where the two lines first differ, or nullopt if they are
+// equal. When one line is a prefix of the other, that is the shorter length.
+inline std::optional<uint64_t> first_diff_column(std::string_view a,
+                                                 std::string_view b) {
+  auto [it_a, it_b] = std::ranges::mismatch(a, b);
+  if (it_a == a.end() && it_b == b.end()) {
+    return std::nullopt;
+  }
+  return it_a - a.begin();
+}
+
+// Maps a line to the key used when comparing lines (dedup, diff). Only the
+// key is normalized; callers keep returning the original lines.
+using LineNormalizer = std::function<std::string(std::string_view)>;
+
+// Replaces each run of ASCII digits with a single '#', so lines differing only
+// in ids, counts or durations compare equal.
+inline std::string mask_digit_runs(std::string_view line) {
+  std::string ret;
+  ret.reserve(line.size());
+  bool in_digits = false;
+  for (char c : line) {
+    bool digit = c >= '0' && c <= '9';
+    if (!digit) {
+      ret.push_back(c);
+    } else if (!in_digits) {
+      ret.push_back('#');
+    }
+    in_digits = digit;
+  }
+  return ret;
+}
+
+// Drops a leading timestamp and the whitespace after it. Recognizes a date
+// token ("2024-01-02", "2024/01/02", "2024-01-02T03:04:05.678Z") optionally
+// followed by a time token ("03:04:05,678"), optionally wrapped in [...].
+// Lines without such a prefix are returned unchanged.
+inline std::string strip_leading_timestamp(std::string_view line) {
+  auto is_timestamp_token = [](std::string_view token) {
+    return !token.empty() && token[0] >= '0' && token[0] <= '9' &&
+           token.find_first_not_of("0123456789-/:.,TZ+") ==
+               std::string_view::npos;
+  };
+  auto skip_blanks = [](std::string_view s) {
+    return s.substr(std::min(s.find_first_not_of(" \t"), s.size()));
+  };
+  auto next_token = [](std::string_view s) {
+    return s.substr(0, s.find_first_of(" \t]"));
+  };
+
+  auto rest = line;
+  bool bracketed = rest.starts_with('[');
+  if (bracketed) {
+    rest.remove_prefix(1);
+  }
+
+  auto date = next_token(rest);
+  auto date_digits =
+      std::ranges::count_if(date, [](char c) { return c >= '0' && c <= '9'; });
+  if (!is_timestamp_token(date) || date_digits < 6 ||
+      date.find_first_of("-/") == std::string_view::npos) {
+    return std::string(line);
+  }
+  rest.remove_prefix(date.size());
+
+  auto after_date = skip_blanks(rest);
+  auto time = next_token(after_date);
+  if (is_timestamp_token(time) && time.find(':') != std::string_view::npos) {
+    rest = after_date.substr(time.size());
+  }
+
+  if (bracketed) {
+    if (!rest.starts_with(']')) {
+      return std::string(line);
+    }
+    rest.remove_prefix(1);
+  }
+  return std::string(skip_blanks(rest));
+}
+
+// strip_leading_timestamp followed by mask_digit_runs.
+inline std::string collapse_timestamps(std::string_view line) {
+  return mask_digit_runs(strip_leading_timestamp(line));
+}
+
+struct DecodedChar {
+  uint32_t length;
+  uint32_t width;
+  bool valid = true;
+};
+
+// Decodes the UTF-8 sequence at `offset`. Invalid sequences (truncated,
+// overlong, surrogates or above U+10FFFF) are consumed one byte at a time with
+// width 1 and valid == false, so callers never stop mid-line.
+constexpr DecodedChar decode_display_char(std::string_view line,
+                                          uint64_t offset) {
+  auto lead = static_cast<unsigned char>(line[offset]);
+  if (lead < 0x80) {
+    return {1, lead == '\n' || lead == '\r' ? 0u : 1u};
+  }
+
+  uint32_t length = 0;
+  char32_t code = 0;
+  char32_t min_code = 0;
+  if ((lead & 0xE0) == 0xC0) {
+    length = 2;
+    code = lead & 0x1F;
+    min_code = 0x80;
+  } else if ((lead & 0xF0) == 0xE0) {
+    length = 3;
+    code = lead & 0x0F;
+    min_code = 0x800;
+  } else if ((lead & 0xF8) == 0xF0) {
+    length = 4;
+    code = lead & 0x07;
+    min_code = 0x10000;
+  } else {
+    return {1, 1, false};
+  }
+  if (offset + length > line.size()) {
+    return {1, 1, false};
+  }
+  for (uint32_t i = 1; i < length; i++) {
+    auto byte = static_cast<unsigned char>(line[offset + i]);
+    if ((byte & 0xC0) != 0x80) {
+      return {1, 1, false};
+    }
+    code = (code << 6) | (byte & 0x3F);
+  }
+  if (code < min_code || (code >= 0xD800 && code <= 0xDFFF) ||
+      code > 0x10FFFF) {
+    return {1, 1, false};
+  }
+
+  // Combining marks, zero-width spaces/joiners/direction marks and variation
+  // selectors take no column of their own.
+  bool zero_width = (code >= 0x0300 && code <= 0x036F) ||
+                    (code >= 0x1AB0 && code <= 0x1AFF) ||
+                    (code >= 0x1DC0 && code <= 0x1DFF) ||
+                    (code >= 0x200B && code <= 0x200F) ||
+                    (code >= 0x20D0 && code <= 0x20FF) ||
+                    (code >= 0xFE00 && code <= 0xFE0F) ||
+                    (code >= 0xFE20 && code <= 0xFE2F);
+  if (zero_width) {
+    return {length, 0};
+  }
+
+  bool wide = (code >= 0x1100 && code <= 0x115F) ||
+              (code >= 0x2E80 && code <= 0xA4CF && code != 0x303F) ||
+              (code >= 0xAC00 && code <= 0xD7A3) ||
+              (code >= 0xF900 && code <= 0xFAFF) ||
+              (code >= 0xFE30 && code <= 0xFE4F) ||
+              (code >= 0xFF00 && code <= 0xFF60) ||
+              (code >= 0xFFE0 && code <= 0xFFE6) ||
+              (code >= 0x1F300 && code <= 0x1F64F) ||
+              (code >= 0x1F900 && code <= 0x1F9FF) ||
+              (code >= 0x20000 && code <= 0x3FFFD);
+  return {length, wide ? 2u : 1u};
+}
+
+// Splits `line` into byte ranges that each fit in `width` display columns.
+// Wide characters count as two columns and tabs advance to the next multiple
+// of `tab_width` within the row. A character wider than the whole row still
+// gets a row of its own. Zero-width characters (including the terminator)
+// never start a row. Always returns at least one range.
+inline std::vector<ByteRange> wrap_line(std::string_view line, uint64_t width,
+                                        uint64_t tab_width) {
+  std::vector<ByteRange> segments;
+  if (width == 0) {
+    segments.push_back({0, line.size()});
+    return segments;
+  }
+  tab_width = std::max<uint64_t>(tab_width, 1);
+
+  uint64_t segment_start = 0;
+  uint64_t column = 0;
+  uint64_t offset = 0;
+  while (offset < line.size()) {
+    auto ch = decode_display_char(line, offset);
+    auto char_width = [&] {
+      return line[offset] == '\t' ? tab_width - column % tab_width
+                                   : uint64_t{ch.width};
+    };
+    if (column > 0 && char_width() > 0 && column + char_width() > width) {
+      segments.push_back({segment_start, offset});
+      segment_start = offset;
+      column = 0;
+    }
+    column += char_width();
+    offset += ch.length;
+  }
+  segments.push_back({segment_start, line.size()});
+  return segments;
+}
+
+// Coalesces overlapping or adjacent spans into maximal runs, sorted by start.
+// Empty spans are dropped.
+inline std::vector<ByteRange> merge_spans(std::vector<ByteRange> spans) {
+  std::ranges::sort(spans, {}, &ByteRange::start);
+  std::vector<ByteRange> merged;
+  for (auto span : spans) {
+    if (span.start >= span.end) {
+      continue;
+    }
+    if (!merged.empty() && span.start <= merged.back().end) {
+      merged.back().end = std::max(merged.back().end, span.end);
+    } else {
+      merged.push_back(span);
+    }
+  }
+  return merged;
+}
+
+// Redistributes a line's match spans over its wrapped segments (as returned
+// by wrap_line). Spans are clipped at segment boundaries and reported relative
+// to the start of each segment, so a span crossing a wrap shows up in both.
+inline std::vector<std::vector<ByteRange>>
+map_spans_to_wrapped(std::span<const ByteRange> spans,
+                     std::span<const ByteRange> segments) {
+  std::vector<std::vector<ByteRange>> mapped(segments.size());
+  for (size_t i = 0; i < segments.size(); i++) {
+    auto segment = segments[i];
+    for (auto span : spans) {
+      auto start = std::max(span.start, segment.start);
+      auto end = std::min(span.end, segment.end);
+      if (start < end) {
+        mapped[i].push_back({start - segment.start, end - segment.start});
+      }
+    }
+  }
+  return mapped;
+}
+
+struct AnsiStyle {
+  // SGR parameters, e.g. "1;31" for bold red.
+  std::string_view sgr = "1;31";
+};
+
+struct StyledRun {
+  ByteRange range;
+  const AnsiStyle *style;
+};
+
+// Emits `line` with each character in the style of the first run it overlaps.
+// `runs` must be sorted and disjoint. Line terminators are never styled, and
+// invalid UTF-8 bytes are replaced with U+FFFD.
+inline std::string render_styled(std::string_view line,
+                                 std::span<const StyledRun> runs) {
+  std::string ret;
+  ret.reserve(line.size());
+
+  const AnsiStyle *current = nullptr;
+  size_t run_idx = 0;
+  uint64_t offset = 0;
+  while (offset < line.size()) {
+    auto ch = decode_display_char(line, offset);
+    uint64_t end = offset + ch.length;
+    while (run_idx < runs.size() && runs[run_idx].range.end <= offset) {
+      run_idx++;
+    }
+    const AnsiStyle *style = nullptr;
+    if (run_idx < runs.size() && runs[run_idx].range.start < end &&
+        line[offset] != '\n' && line[offset] != '\r') {
+      style = runs[run_idx].style;
+    }
+    if (style != current) {
+      if (current != nullptr) {
+        ret.append("\x1b[0m");
+      }
+      if (style != nullptr) {
+        ret.append(std::format("\x1b[{}m", style->sgr));
+      }
+      current = style;
+    }
+    if (ch.valid) {
+      ret.append(line.substr(offset, ch.length));
+    } else {
+      ret.append("\xEF\xBF\xBD");
+    }
+    offset = end;
+  }
+  if (current != nullptr) {
+    ret.append("\x1b[0m");
+  }
+  return ret;
+}
+
+// Wraps the matched spans of `line` in `style`, widening highlights to whole
+// UTF-8 characters.
+inline std::string render_line_ansi(std::string_view line,
+                                    std::span<const ByteRange> spans,
+                                    AnsiStyle style) {
+  std::vector<StyledRun> runs;
+  for (auto span : merge_spans({spans.begin(), spans.end()})) {
+    runs.push_back({span, &style});
+  }
+  return render_styled(line, runs);
+}
+
+struct PatternSpans {
+  uint64_t pattern_id;
+  std::vector<ByteRange> spans;
+};
+
+// Like render_line_ansi, but each pattern's spans use styles[pattern_id],
+// cycling when there are fewer styles than patterns. Where spans of
+// different patterns overlap, the later entry in `matches_by_pattern` wins.
+inline std::string
+render_line_multi(std::string_view line,
+                  std::span<const PatternSpans> matches_by_pattern,
+                  std::span<const AnsiStyle> styles) {
+  if (styles.empty()) {
+    return render_styled(line, {});
+  }
+
+  // Sweep span boundaries, tracking how many spans of each entry are open;
+  // the highest open entry index styles the stretch up to the next boundary.
+  struct Event {
+    uint64_t pos;
+    size_t entry;
+    int delta;
+  };
+  std::vector<Event> events;
+  for (size_t entry = 0; entry < matches_by_pattern.size(); entry++) {
+    for (auto span : matches_by_pattern[entry].spans) {
+      if (span.start < span.end) {
+        events.push_back({span.start, entry, 1});
+        events.push_back({span.end, entry, -1});
+      }
+    }
+  }
+  std::ranges::sort(events, {}, &Event::pos);
+
+  std::vector<StyledRun> runs;
+  std::map<size_t, uint32_t> open;
+  for (size_t i = 0; i < events.size();) {
+    auto pos = events[i].pos;
+    for (; i < events.size() && events[i].pos == pos; i++) {
+      auto &count = open[events[i].entry];
+      count += events[i].delta;
+      if (count == 0) {
+        open.erase(events[i].entry);
+      }
+    }
+    if (open.empty() || i == events.size()) {
+      continue;
+    }
+    auto pattern_id = matches_by_pattern[open.rbegin()->first].pattern_id;
+    const auto *style = &styles[pattern_id % styles.size()];
+    ByteRange range{pos, events[i].pos};
+    if (!runs.empty() && runs.back().style == style &&
+        runs.back().range.end == range.start) {
+      runs.back().range.end = range.end;
+    } else {
+      runs.push_back({range, style});
+    }
+  }
+  return render_styled(line, runs);
+}
@@ -0,0 +1,263 @@
+#undef NDEBUG
+#include <cassert>
+
+#include "lognav.h"
+
+// Rows decide the order; columns only matter when rows are equal.
+static_assert(Position{1, 9} < Position{2, 0});
+static_assert(Position{2, 0} < Position{2, 3});
+static_assert(Position{2, 3} > Position{1, 100});
+static_assert(Position{3, 0}.clamp({1, 9}, {2, 3}) == Position{2, 3});
+static_assert(Position{0, 5}.clamp({1, 9}, {2, 3}) == Position{1, 9});
+static_assert(Position{2, 1}.clamp({1, 9}, {2, 3}) == Position{2, 1});
+static_assert(Position{3, 0}.clamp({5, 0}, {1, 0}) == Position{3, 0});
+static_assert(Position{6, 0}.clamp({5, 0}, {1, 0}) == Position{5, 0});
+static_assert(Position{1, 9}.is_empty_range({1, 9}));
+static_assert(!Position{1, 9}.is_empty_range({2, 9}));
+
+static_assert(decode_display_char("a", 0).valid);
+static_assert(decode_display_char("\xC2\x80", 0).length == 2);
+static_assert(decode_display_char("\xE4\xB8\xAD", 0).width == 2);
+static_assert(decode_display_char("\xF4\x8F\xBF\xBF", 0).valid);
+// U+0301 COMBINING ACUTE ACCENT, U+200B ZERO WIDTH SPACE, U+FE0F VS16.
+static_assert(decode_display_char("\xCC\x81", 0).width == 0);
+static_assert(decode_display_char("\xE2\x80\x8B", 0).width == 0);
+static_assert(decode_display_char("\xEF\xB8\x8F", 0).width == 0);
+// Overlong, surrogate, and beyond U+10FFFF (including F5-F7 leads).
+static_assert(!decode_display_char("\xC0\x80", 0).valid);
+static_assert(!decode_display_char("\xE0\x80\x80", 0).valid);
+static_assert(!decode_display_char("\xF0\x80\x80\x80", 0).valid);
+static_assert(!decode_display_char("\xED\xA0\x80", 0).valid);
+static_assert(!decode_display_char("\xF4\x90\x80\x80", 0).valid);
+static_assert(!decode_display_char("\xF5\x80\x80\x80", 0).valid);
+static_assert(decode_display_char("\xF5\x80\x80\x80", 0).length == 1);
+
+static void test_skip_bom() {
+  auto chunk = Chunk::make("\xEF\xBB\xBFhello\nworld", {.skip_bom = true});
+  assert(chunk.content_start_offset() == 3);
+  assert(chunk.get_line_count() == 2);
+  assert(chunk.get_first_line_view() == "hello\n");
+  assert(chunk.start_offset[1] == 9);
+
+  auto bom_only = Chunk::make("\xEF\xBB\xBF", {.skip_bom = true});
+  assert(bom_only.get_line_count() == 0);
+  assert(bom_only.last_line_complete());
+  assert(bom_only.calc_end({1, 2}) == (Position{1, 2}));
+
+  auto kept = Chunk::make("\xEF\xBB\xBFhello\n");
+  assert(kept.content_start_offset() == 0);
+  assert(kept.get_first_line_view() == "\xEF\xBB\xBFhello\n");
+}
+
+static void test_wrap_line() {
+  using Segments = std::vector<ByteRange>;
+
+  // "中文字" at an odd width: the third character does not fit in column 5.
+  assert(wrap_line("\xE4\xB8\xAD\xE6\x96\x87\xE5\xAD\x97", 5, 8) ==
+         (Segments{{0, 6}, {6, 9}}));
+  // A tab mid-row advances to the next stop (column 4).
+  assert(wrap_line("a\tbcdef", 6, 4) == (Segments{{0, 4}, {4, 7}}));
+  // A character wider than the row gets a row of its own.
+  assert(wrap_line("\xE4\xB8\xAD" "a", 1, 8) == (Segments{{0, 3}, {3, 4}}));
+  // A trailing "\r\n" stays on the last row.
+  assert(wrap_line("abcd\r\n", 4, 8) == (Segments{{0, 6}}));
+  assert(wrap_line("abcdef\r\n", 4, 8) == (Segments{{0, 4}, {4, 8}}));
+  assert(wrap_line("", 4, 8) == (Segments{{0, 0}}));
+}
+
+static void test_wrap_line_keeps_zero_width_on_row() {
+  using Segments = std::vector<ByteRange>;
+
+  // The terminator stays with a character that overflowed the row.
+  assert(wrap_line("\xE4\xB8\xAD\n", 1, 8) == (Segments{{0, 4}}));
+  assert(wrap_line("a\t\r\n", 1, 8) == (Segments{{0, 1}, {1, 4}}));
+  // NFD "éé": each combining accent stays with its base character.
+  assert(wrap_line("e\xCC\x81" "e\xCC\x81", 1, 8) ==
+         (Segments{{0, 3}, {3, 6}}));
+  assert(wrap_line("e\xCC\x81" "e\xCC\x81", 2, 8) == (Segments{{0, 6}}));
+}
+
+static void test_map_spans_to_wrapped() {
+  using Segments = std::vector<ByteRange>;
+  Segments segments{{0, 3}, {3, 6}};
+
+  // A span crossing the wrap is clipped into both rows.
+  Segments crossing{{1, 5}};
+  assert(map_spans_to_wrapped(crossing, segments) ==
+         (std::vector<Segments>{{{1, 3}}, {{0, 2}}}));
+  // A span inside one row, and one outside every row.
+  Segments inside_and_outside{{4, 5}, {7, 9}};
+  assert(map_spans_to_wrapped(inside_and_outside, segments) ==
+         (std::vector<Segments>{{}, {{1, 2}}}));
+}
+
+static void test_normalizers() {
+  assert(mask_digit_runs("took 125ms for id 7") == "took #ms for id #");
+  assert(mask_digit_runs("") == "");
+
+  assert(strip_leading_timestamp("2024-01-02T03:04:05.678Z boot") == "boot");
+  assert(strip_leading_timestamp("2024-01-02 03:04:05,678 INFO x") ==
+         "INFO x");
+  assert(strip_leading_timestamp("[2024/01/02 03:04:05] x") == "x");
+  assert(strip_leading_timestamp("2024-01-02 42 items") == "42 items");
+  // Not a timestamp prefix: short numbers, plain words, unclosed brackets.
+  assert(strip_leading_timestamp("1-2 x") == "1-2 x");
+  assert(strip_leading_timestamp("INFO 2024-01-02") == "INFO 2024-01-02");
+  assert(strip_leading_timestamp("[2024-01-02 x") == "[2024-01-02 x");
+
+  LineNormalizer normalize = collapse_timestamps;
+  assert(normalize("2024-01-02 03:04:05 retry 3 of 5") ==
+         normalize("2024-01-03 11:00:00 retry 4 of 5"));
+  assert(normalize("2024-01-02 03:04:05 retry") !=
+         normalize("2024-01-02 03:04:05 abort"));
+}
+
+static void test_lone_cr_split_across_chunks() {
+  // The '\r' at the end of `head` may be half of a "\r\n", so it must not
+  // end the line; the '\n' heading `tail` then completes it.
+  auto head = Chunk::make("a\rb\r", {.lone_cr_is_newline = true});
+  auto tail = Chunk::make("\nc\r\n", {.lone_cr_is_newline = true});
+  assert(head.get_line_count() == 2);
+  assert(head.get_line_content(0) == "a\r");
+  assert(head.get_line_content(1) == "b\r");
+  assert(head.ends_with_pending_cr());
+  assert(!head.last_line_complete());
+  assert(tail.get_line_count() == 2);
+  assert(tail.get_line_content(0) == "\n");
+  assert(tail.get_line_content(1) == "c\r\n");
+  assert(!tail.ends_with_pending_cr());
+
+  auto plain = Chunk::make("a\r");
+  assert(!plain.ends_with_pending_cr());
+}
+
+static LineEndingStats sum_line_ending_stats(std::string_view head,
+                                             std::string_view tail,
+                                             ChunkOptions options) {
+  auto stats = Chunk::make(std::string(head), options).line_ending_stats();
+  stats += Chunk::make(std::string(tail), options).line_ending_stats();
+  return stats;
+}
+
+static void test_line_ending_stats_across_seam() {
+  // A CRLF file split between '\r' and '\n' is not mixed.
+  for (bool lone_cr : {false, true}) {
+    auto stats = sum_line_ending_stats("a\r\nb\r", "\nc\r\n",
+                                       {.lone_cr_is_newline = lone_cr});
+    assert(stats.crlf == 3);
+    assert(stats.lf == 0);
+    assert(stats.cr == 0);
+    assert(!stats.mixed());
+  }
+
+  // A lone CR at the seam stays a CR.
+  auto stats = sum_line_ending_stats("a\r\nb\r", "c\r\n",
+                                     {.lone_cr_is_newline = true});
+  assert(stats.crlf == 2);
+  assert(stats.cr == 1);
+  assert(stats.mixed());
+
+  // Without lone-CR mode, "b\rc\r\n" is a single CRLF line.
+  stats = sum_line_ending_stats("a\r\nb\r", "c\r\n", {});
+  assert(stats.crlf == 2);
+  assert(stats.cr == 0);
+  assert(!stats.mixed());
+
+  // An empty chunk between the halves of a CRLF keeps the seam state.
+  for (bool lone_cr : {false, true}) {
+    ChunkOptions options{.lone_cr_is_newline = lone_cr};
+    auto stats = Chunk::make("a\r", options).line_ending_stats();
+    stats += Chunk::make("", options).line_ending_stats();
+    stats += Chunk::make("\nb\n", options).line_ending_stats();
+    assert(stats.crlf == 1);
+    assert(stats.lf == 1);
+    assert(stats.cr == 0);
+  }
+
+  // Summing into default-constructed stats takes the first chunk's flags.
+  LineEndingStats total;
+  total += Chunk::make("\nx\r").line_ending_stats();
+  assert(total.starts_with_lf);
+  assert(total.ends_with_cr);
+
+  stats = sum_line_ending_stats("a\nb\r\n", "c\n", {});
+  assert(stats.lf == 2);
+  assert(stats.crlf == 1);
+  assert(stats.mixed());
+}
+
+static void test_merge_spans() {
+  using Spans = std::vector<ByteRange>;
+
+  // "foo|foobar" style overlaps on "foobar foo".
+  assert(merge_spans({{7, 10}, {0, 6}, {0, 3}}) == (Spans{{0, 6}, {7, 10}}));
+  // Back-to-back matches join into one run.
+  assert(merge_spans({{3, 6}, {0, 3}, {6, 9}}) == (Spans{{0, 9}}));
+  // A span nested in another disappears into it.
+  assert(merge_spans({{0, 8}, {2, 4}}) == (Spans{{0, 8}}));
+  // Empty spans are dropped, even next to a real one.
+  assert(merge_spans({{12, 12}, {5, 5}, {5, 7}}) == (Spans{{5, 7}}));
+  assert(merge_spans({}).empty());
+}
+
+static void test_render_line_ansi_invalid_utf8() {
+  // Each invalid byte becomes its own U+FFFD.
+  for (std::string_view bad :
+       {"\xC0\x80", "\xED\xA0\x80", "\xF5\x80\x80\x80",
+        "\xF4\x90\x80\x80"}) {
+    std::string expected;
+    for (size_t i = 0; i < bad.size(); i++) {
+      expected.append("\xEF\xBF\xBD");
+    }
+    assert(render_line_ansi(bad, {}, {}) == expected);
+  }
+  assert(render_line_ansi("\xE4\xB8\xAD", {}, {}) == "\xE4\xB8\xAD");
+}
+
+static void test_render_line_ansi() {
+  // Spans are widened to whole characters; the terminator stays plain.
+  std::vector<ByteRange> spans{{1, 3}, {4, 5}, {5, 7}};
+  assert(render_line_ansi("ab\xE4\xB8\xAD" "d\n", spans, {}) ==
+         "a\x1b[1;31mb\xE4\xB8\xAD" "d\x1b[0m\n");
+}
+
+static void test_render_line_multi() {
+  std::vector<AnsiStyle> styles{{"31"}, {"32"}};
+
+  // The later entry wins the overlap; differently styled neighbours are
+  // separated by a reset.
+  std::vector<PatternSpans> matches{{0, {{0, 3}}}, {1, {{2, 5}}}};
+  assert(render_line_multi("abcdef\n", matches, styles) ==
+         "\x1b[31mab\x1b[0m\x1b[32mcde\x1b[0mf\n");
+  std::vector<PatternSpans> reversed{{1, {{2, 5}}}, {0, {{0, 3}}}};
+  assert(render_line_multi("abcdef\n", reversed, styles) ==
+         "\x1b[31mabc\x1b[0m\x1b[32mde\x1b[0mf\n");
+
+  // Pattern 2 cycles back to styles[0]; an entry's own overlapping spans
+  // and a span nested inside a later entry's span are handled.
+  std::vector<PatternSpans> cycled{{2, {{0, 2}, {1, 4}}}, {1, {{1, 2}}}};
+  assert(render_line_multi("abcdef", cycled, styles) ==
+         "\x1b[31ma\x1b[0m\x1b[32mb\x1b[0m\x1b[31mcd\x1b[0mef");
+
+  // Touching spans of the same style form one run.
+  std::vector<PatternSpans> touching{{0, {{0, 2}}}, {0, {{2, 4}}}};
+  assert(render_line_multi("abcdef", touching, styles) ==
+         "\x1b[31mabcd\x1b[0mef");
+
+  assert(render_line_multi("abc", matches, {}) == "abc");
+}
+
+int main() {
+  test_skip_bom();
+  test_wrap_line();
+  test_wrap_line_keeps_zero_width_on_row();
+  test_map_spans_to_wrapped();
+  test_normalizers();
+  test_lone_cr_split_across_chunks();
+  test_line_ending_stats_across_seam();
+  test_merge_spans();
+  test_render_line_ansi_invalid_utf8();
+  test_render_line_ansi();
+  test_render_line_multi();
+  return 0;
+}